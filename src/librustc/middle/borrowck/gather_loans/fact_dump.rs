@@ -0,0 +1,158 @@
+// Copyright 2012 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// ----------------------------------------------------------------------
+// Fact dumping
+//
+// When fact dumping is enabled (see `BorrowckCtxt::fact_tables`), gather
+// loans records relations meant to seed an external Datalog-style solver
+// that double-checks the conclusions borrowck reaches on its own. Every
+// statement and block-terminator visited by gather-loans is given a
+// stable `ProgramPoint` from `BorrowckCtxt::stmt_map` (the single shared
+// counter described in `stmt_index.rs`), and we accumulate three tables
+// keyed by those points. Two of the three are explicitly incomplete --
+// see the per-relation notes below -- and need follow-up work before a
+// solver could answer real control-flow or liveness questions from them:
+//
+// - `stmt_order_edge(point_from, point_to)`: the order gather-loans visits
+//   statements (and the trailing terminator expression, if any) *within a
+//   single block*. FIXME: this is NOT a control-flow graph -- branching
+//   constructs (`if`, `while`, `match`, ...) and the edges between a block
+//   and its parent are not represented, so a solver cannot reconstruct
+//   real control flow from this relation alone, only straight-line order
+//   inside one block. A real `cfg_edge` relation needs a follow-up pass
+//   that walks branches and block boundaries, not just `add_stmt_to_map`.
+// - `loan_issued_at(loan_id, region, point)`: where each loan is created.
+// - `region_live_at(region, point)`: FIXME: only the loan's `gen_scope`
+//   and `kill_scope` boundary points are recorded here, not every point
+//   in between where the region is actually live. This is a seed for a
+//   real liveness dataflow pass, not liveness itself; a solver cannot
+//   answer "is this region live at point P" from this relation for any
+//   P other than those two boundaries.
+//
+// Each table is a flat list of fixed-arity integer tuples; `dump()` writes
+// each one to its own tab-separated `<name>.facts` file under a directory,
+// the layout a Datalog engine such as Souffle expects for input relations.
+// Regions do not have a stable integer id of their own, so we intern their
+// `Repr` string the first time we see them and use that id from then on.
+
+use core::prelude::*;
+
+use middle::ty;
+use syntax::ast;
+use util::ppaux::Repr;
+
+use core::hashmap::HashMap;
+use core::io;
+use core::os;
+
+pub type ProgramPoint = uint;
+
+pub struct FactTables {
+    priv region_ids: HashMap<~str, uint>,
+    stmt_order_edges: ~[(ProgramPoint, ProgramPoint)],
+    loan_issued_at: ~[(uint, uint, ProgramPoint)],
+    region_live_at: ~[(uint, ProgramPoint)]
+}
+
+pub impl FactTables {
+    fn new() -> FactTables {
+        FactTables {
+            region_ids: HashMap::new(),
+            stmt_order_edges: ~[],
+            loan_issued_at: ~[],
+            region_live_at: ~[]
+        }
+    }
+
+    fn add_stmt_order_edge(&mut self, from: ProgramPoint, to: ProgramPoint) {
+        self.stmt_order_edges.push((from, to));
+    }
+
+    fn add_loan_issued_at(&mut self,
+                          tcx: ty::ctxt,
+                          loan_id: uint,
+                          region: ty::Region,
+                          point: ProgramPoint) {
+        let region_id = self.id_of_region(tcx, region);
+        self.loan_issued_at.push((loan_id, region_id, point));
+    }
+
+    fn add_region_live_at(&mut self,
+                          tcx: ty::ctxt,
+                          region: ty::Region,
+                          point: ProgramPoint) {
+        let region_id = self.id_of_region(tcx, region);
+        self.region_live_at.push((region_id, point));
+    }
+
+    priv fn id_of_region(&mut self, tcx: ty::ctxt, region: ty::Region) -> uint {
+        self.id_of_key(region.repr(tcx))
+    }
+
+    priv fn id_of_key(&mut self, key: ~str) -> uint {
+        match self.region_ids.find(&key) {
+            Some(&id) => return id,
+            None => {}
+        }
+        let id = self.region_ids.len();
+        self.region_ids.insert(key, id);
+        id
+    }
+
+    /// Writes each relation to `dir/<name>.facts`, one tab-separated
+    /// tuple per line. Creates `dir` if it does not already exist.
+    fn dump(&self, dir: &Path) {
+        if !os::path_exists(dir) {
+            os::mkdir_recursive(dir, 0o755u);
+        }
+        write_relation2(&dir.push("stmt_order_edge.facts"), self.stmt_order_edges);
+        write_relation3(&dir.push("loan_issued_at.facts"), self.loan_issued_at);
+        write_relation2(&dir.push("region_live_at.facts"), self.region_live_at);
+    }
+}
+
+fn write_relation2(path: &Path, rows: &[(uint, uint)]) {
+    let writer = io::file_writer(path, [io::Create, io::Truncate]).unwrap();
+    for rows.each |&(a, b)| {
+        writer.write_line(fmt!("%u\t%u", a, b));
+    }
+}
+
+fn write_relation3(path: &Path, rows: &[(uint, uint, uint)]) {
+    let writer = io::file_writer(path, [io::Create, io::Truncate]).unwrap();
+    for rows.each |&(a, b, c)| {
+        writer.write_line(fmt!("%u\t%u\t%u", a, b, c));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FactTables;
+
+    #[test]
+    fn stmt_order_edges_accumulate_in_push_order() {
+        let mut tables = FactTables::new();
+        tables.add_stmt_order_edge(0, 1);
+        tables.add_stmt_order_edge(1, 2);
+        assert_eq!(tables.stmt_order_edges, ~[(0u, 1u), (1u, 2u)]);
+    }
+
+    #[test]
+    fn region_key_interning_reuses_ids_for_the_same_key() {
+        let mut tables = FactTables::new();
+        let a = tables.id_of_key(~"'a");
+        let b = tables.id_of_key(~"'b");
+        let a_again = tables.id_of_key(~"'a");
+        assert_eq!(a, 0u);
+        assert_eq!(b, 1u);
+        assert_eq!(a_again, a);
+    }
+}
@@ -15,6 +15,40 @@
 // set of loans that are required at any point.  These are sorted according to
 // their associated scopes.  In phase two, checking loans, we will then make
 // sure that all of these loans are honored.
+//
+// When `-Z borrowck-dump-facts` is passed, `BorrowckCtxt` carries an extra
+// `fact_tables: Option<@mut fact_dump::FactTables>` (backed by
+// `fact_dump_dir`), so this pass can also emit the `stmt_order_edge`,
+// `loan_issued_at` and `region_live_at` relations. These are seed data
+// for an external Datalog solver, not finished control-flow or liveness
+// facts -- `stmt_order_edge` only orders statements within a single
+// block, and `region_live_at` only records a region's two endpoints, not
+// the points in between. See `fact_dump.rs` for exactly what's missing
+// and what a follow-up pass would need to add.
+//
+// `BorrowckCtxt::stmt_map` is a `stmt_index::StmtIndex`: besides the
+// membership query ("is this id a statement?"), it hands out the
+// `ProgramPoint`s written into every `fact_dump` relation, from a single
+// shared counter, so two points appearing in different relations are
+// always directly comparable. Statement ids go through `stmt_map.insert`,
+// which also assigns a dense `local_index` bijection relative to the
+// id's own enclosing block (for passes that want "what comes before this
+// statement, in its own block?"). Ids that are not statements -- a
+// block's trailing expression, and the borrow/scope ids
+// `guarantee_valid` needs a point for -- go through
+// `stmt_map.next_shared_point` instead, which draws from the same
+// counter without being mistaken for a statement by `contains` or
+// `local_index_of`. See `stmt_index.rs`.
+//
+// `BorrowckCtxt::stmt_passes` is a `stmt_passes::StmtPasses` (a
+// `Registry`-mutated list of `@fn((@ast::stmt, ast::node_id,
+// @BorrowckCtxt))` closures, each taking one tuple argument) that plugins
+// can push onto at load time, same
+// as `rustc::plugin::Registry` does for syntax extensions and lint passes
+// elsewhere in the compiler. Registered passes run, in registration
+// order, alongside the built-in `stmt_map` insertion as gather-loans
+// walks the body, so a plugin's flow analysis rides the existing
+// traversal instead of re-walking the AST itself. See `stmt_passes.rs`.
 
 use core::prelude::*;
 
@@ -33,8 +67,11 @@ use syntax::codemap::span;
 use syntax::print::pprust;
 use syntax::visit;
 
+mod fact_dump;
 mod lifetime;
 mod restrictions;
+pub mod stmt_index;
+pub mod stmt_passes;
 
 /// Context used while gathering loans:
 ///
@@ -67,7 +104,16 @@ struct GatherLoanCtxt {
     id_range: id_range,
     all_loans: @mut ~[Loan],
     item_ub: ast::node_id,
-    repeating_ids: ~[ast::node_id]
+    repeating_ids: ~[ast::node_id],
+    // The block we are currently walking; every id gather-loans inserts
+    // into `stmt_map` while here is credited to this block for the
+    // purposes of `local_index_of`.
+    current_block: ast::node_id,
+    // The point of the most recently visited statement in the block we
+    // are currently walking, or `None` at the head of a block. Used to
+    // link consecutive statements into `stmt_order_edge` facts when fact
+    // dumping is enabled; see `fact_dump.rs`.
+    last_point: Option<fact_dump::ProgramPoint>
 }
 
 pub fn gather_loans(bccx: @BorrowckCtxt,
@@ -77,7 +123,9 @@ pub fn gather_loans(bccx: @BorrowckCtxt,
         id_range: id_range::max(),
         all_loans: @mut ~[],
         item_ub: body.node.id,
-        repeating_ids: ~[body.node.id]
+        repeating_ids: ~[body.node.id],
+        current_block: body.node.id,
+        last_point: None
     };
     let v = visit::mk_vt(@visit::Visitor {visit_expr: gather_loans_in_expr,
                                           visit_block: gather_loans_in_block,
@@ -86,6 +134,10 @@ pub fn gather_loans(bccx: @BorrowckCtxt,
                                           visit_pat: add_pat_to_id_range,
                                           .. *visit::default_visitor()});
     (v.visit_block)(body, glcx, v);
+    // `bccx.fact_tables` accumulates across every body borrowck visits in
+    // this crate; the crate-level driver in `borrowck::check_crate` is
+    // responsible for calling `FactTables::dump` once checking completes,
+    // so that a function's facts aren't overwritten by the next one.
     return (glcx.id_range, glcx.all_loans);
 }
 
@@ -127,7 +179,32 @@ fn gather_loans_in_block(blk: &ast::blk,
                          this: @mut GatherLoanCtxt,
                          vt: visit::vt<@mut GatherLoanCtxt>) {
     this.id_range.add(blk.node.id);
+
+    // `stmt_order_edge` facts only link statements (and the trailing
+    // terminator expression, if any) within the same block, so reset the
+    // chain at each nested block and restore it on the way out.
+    let outer_block = this.current_block;
+    let outer_last_point = this.last_point;
+    this.current_block = blk.node.id;
+    this.last_point = None;
     visit::visit_block(blk, this, vt);
+
+    // The block's trailing, no-semicolon expression (if any) never goes
+    // through `add_stmt_to_map`, since the visitor reaches it via
+    // `visit_expr` rather than `visit_stmt` -- give it a point here so it
+    // still shows up as the last entry in this block's statement order.
+    // It is not a statement, so its point comes from `next_shared_point`.
+    for blk.node.expr.each |_| {
+        for this.bccx.fact_tables.each |&tables| {
+            let point = this.bccx.stmt_map.next_shared_point();
+            for this.last_point.each |&prev_point| {
+                tables.add_stmt_order_edge(prev_point, point);
+            }
+        }
+    }
+
+    this.current_block = outer_block;
+    this.last_point = outer_last_point;
 }
 
 fn gather_loans_in_expr(ex: @ast::expr,
@@ -383,6 +460,28 @@ pub impl GatherLoanCtxt {
         debug!("guarantee_valid(borrow_id=%?), loan=%s",
                borrow_id, loan.repr(self.tcx()));
 
+        // Record where this loan is issued and where its region is live,
+        // so an external solver can check our work. `gen_scope`/`kill_scope`
+        // bound the region's liveness; the points in between are the
+        // dataflow pass's job to fill in, not ours, so we only seed the
+        // two ends we already know for certain.
+        for self.bccx.fact_tables.each |&tables| {
+            // `borrow_id` is an expression id, not necessarily the
+            // enclosing statement's, and `gen_scope`/`kill_scope` may
+            // name a scope in an ancestor block entirely -- none of the
+            // three are statements, so mint their points from the
+            // counter directly (`next_shared_point`) instead of
+            // `stmt_map.insert`, which would otherwise make `contains`
+            // and `local_index_of` answer for ids that were never
+            // actually visited as statements.
+            let issued_at = self.bccx.stmt_map.next_shared_point();
+            tables.add_loan_issued_at(self.tcx(), loan.index, loan_region, issued_at);
+            let gen_point = self.bccx.stmt_map.next_shared_point();
+            let kill_point = self.bccx.stmt_map.next_shared_point();
+            tables.add_region_live_at(self.tcx(), loan_region, gen_point);
+            tables.add_region_live_at(self.tcx(), loan_region, kill_point);
+        }
+
         // let loan_path = loan.loan_path;
         // let loan_gen_scope = loan.gen_scope;
         // let loan_kill_scope = loan.kill_scope;
@@ -628,7 +727,25 @@ fn add_stmt_to_map(stmt: @ast::stmt,
                    vt: visit::vt<@mut GatherLoanCtxt>) {
     match stmt.node {
         ast::stmt_expr(_, id) | ast::stmt_semi(_, id) => {
-            this.bccx.stmt_map.insert(id);
+            let point = this.bccx.stmt_map.insert(this.current_block, id);
+
+            // When fact dumping is enabled, chain this statement's point
+            // to the previous statement in the block with a
+            // `stmt_order_edge` fact.
+            for this.bccx.fact_tables.each |&tables| {
+                for this.last_point.each |&prev_point| {
+                    tables.add_stmt_order_edge(prev_point, point);
+                }
+                this.last_point = Some(point);
+            }
+
+            // Run any passes third-party plugins registered via
+            // `Registry::register_stmt_pass`, after the built-in pass.
+            this.bccx.stmt_passes.run_all((stmt, id, this.bccx));
+        }
+        ast::stmt_decl(_, id) => {
+            // `let` statements get no point, but plugins still see them.
+            this.bccx.stmt_passes.run_all((stmt, id, this.bccx));
         }
         _ => ()
     }
@@ -0,0 +1,225 @@
+// Copyright 2012 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// ----------------------------------------------------------------------
+// Statement index
+//
+// `add_stmt_to_map` used to record statements in a plain `HashSet`, which
+// could only answer "is this id a statement?". `StmtIndex` keeps that
+// membership query but also hands every inserted id a `point`, so later
+// passes (and `fact_dump`) can reason about order instead of just
+// membership.
+//
+// Two different questions need two different numbering schemes, so
+// `StmtIndex` tracks both:
+//
+// - `point`: a single counter shared by every id inserted, regardless of
+//   which block it came from. This is the id written into `fact_dump`'s
+//   relations, so that a point appearing in `loan_issued_at` and one
+//   appearing in `stmt_order_edge` are directly comparable -- there is
+//   only ever one counter handing out points.
+// - `local_index`: the 0-based position of an id among the other ids
+//   inserted under the same enclosing block, i.e. a bijection between
+//   `0..n` and the statement ids of that one block. This is what a
+//   flow-sensitive diagnostic wants when it asks "what comes before this
+//   statement, in its own block?".
+//
+// Both are assigned incrementally as the gather-loans visitor descends,
+// in visitation order.
+//
+// `guarantee_valid` also needs points for ids that are *not* statements
+// (a borrow expression id, or a gen/kill scope id that may belong to an
+// ancestor block entirely). Folding those into `insert` would make
+// `contains`/`local_index_of` lie about what's actually a statement, so
+// `next_shared_point` hands out a point from the very same counter
+// without recording anything in `entries`/`block_ids` -- it only
+// advances the counter `insert` also draws from.
+
+use core::prelude::*;
+
+use syntax::ast;
+
+use core::hashmap::HashMap;
+
+struct Entry {
+    point: uint,
+    block: ast::node_id,
+    local_index: uint
+}
+
+pub struct StmtIndex {
+    priv next_point: uint,
+    priv entries: HashMap<ast::node_id, Entry>,
+    priv block_ids: HashMap<ast::node_id, ~[ast::node_id]>
+}
+
+pub impl StmtIndex {
+    fn new() -> StmtIndex {
+        StmtIndex {
+            next_point: 0,
+            entries: HashMap::new(),
+            block_ids: HashMap::new()
+        }
+    }
+
+    /// Assigns `id` a point and a `local_index` within `block` if it
+    /// hasn't been seen before (idempotent otherwise), and returns the
+    /// point -- the single counter every relation `gather_loans` feeds
+    /// into `fact_dump` is keyed by.
+    fn insert(&mut self, block: ast::node_id, id: ast::node_id) -> uint {
+        match self.entries.find(&id) {
+            Some(entry) => return entry.point,
+            None => {}
+        }
+
+        let point = self.next_point;
+        self.next_point += 1;
+
+        let local_index = match self.block_ids.find_mut(&block) {
+            Some(ids) => {
+                let local_index = ids.len();
+                ids.push(id);
+                local_index
+            }
+            None => {
+                self.block_ids.insert(block, ~[id]);
+                0
+            }
+        };
+
+        self.entries.insert(id, Entry {
+            point: point,
+            block: block,
+            local_index: local_index
+        });
+        point
+    }
+
+    fn contains(&self, id: ast::node_id) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    fn point_of(&self, id: ast::node_id) -> Option<uint> {
+        match self.entries.find(&id) {
+            Some(entry) => Some(entry.point),
+            None => None
+        }
+    }
+
+    /// The id's 0-based position among the statements of its own
+    /// enclosing block.
+    fn local_index_of(&self, id: ast::node_id) -> Option<uint> {
+        match self.entries.find(&id) {
+            Some(entry) => Some(entry.local_index),
+            None => None
+        }
+    }
+
+    fn id_at(&self, block: ast::node_id, local_index: uint) -> ast::node_id {
+        self.block_ids.get(&block)[local_index]
+    }
+
+    /// Hands out the next point from the shared counter without
+    /// recording it as a statement anywhere. For ids `insert` must never
+    /// see -- borrow expression ids, gen/kill scope ids -- that still
+    /// need a point comparable to the ones `insert` assigns.
+    fn next_shared_point(&mut self) -> uint {
+        let point = self.next_point;
+        self.next_point += 1;
+        point
+    }
+
+    /// One past the highest point handed out by either `insert` or
+    /// `next_shared_point` -- not simply a statement count once the
+    /// latter has been used.
+    fn len(&self) -> uint {
+        self.next_point
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StmtIndex;
+    use syntax::ast;
+
+    #[test]
+    fn assigns_dense_points_in_insertion_order() {
+        let mut index = StmtIndex::new();
+        assert_eq!(index.insert(0, 10), 0u);
+        assert_eq!(index.insert(0, 11), 1u);
+        assert_eq!(index.insert(1, 12), 2u);
+        assert_eq!(index.len(), 3u);
+    }
+
+    #[test]
+    fn reinserting_the_same_id_is_idempotent() {
+        let mut index = StmtIndex::new();
+        let first = index.insert(0, 10);
+        let second = index.insert(0, 10);
+        assert_eq!(first, second);
+        assert_eq!(index.len(), 1u);
+    }
+
+    #[test]
+    fn membership_query_matches_insertions() {
+        let mut index = StmtIndex::new();
+        index.insert(0, 10);
+        assert!(index.contains(10));
+        assert!(!index.contains(11));
+    }
+
+    #[test]
+    fn local_index_is_a_bijection_per_block() {
+        let mut index = StmtIndex::new();
+        index.insert(0, 10);
+        index.insert(0, 11);
+        index.insert(1, 12);
+        index.insert(1, 13);
+
+        assert_eq!(index.local_index_of(10), Some(0u));
+        assert_eq!(index.local_index_of(11), Some(1u));
+        // A fresh block starts its own bijection back at 0, even though
+        // the shared `point` counter keeps climbing.
+        assert_eq!(index.local_index_of(12), Some(0u));
+        assert_eq!(index.local_index_of(13), Some(1u));
+
+        assert_eq!(index.id_at(0, 0), 10);
+        assert_eq!(index.id_at(0, 1), 11);
+        assert_eq!(index.id_at(1, 0), 12);
+        assert_eq!(index.id_at(1, 1), 13);
+    }
+
+    #[test]
+    fn point_of_unknown_id_is_none() {
+        let index = StmtIndex::new();
+        assert_eq!(index.point_of(42), None);
+        assert_eq!(index.local_index_of(42), None);
+    }
+
+    #[test]
+    fn next_shared_point_draws_from_the_same_counter_as_insert() {
+        let mut index = StmtIndex::new();
+        assert_eq!(index.insert(0, 10), 0u);
+        assert_eq!(index.next_shared_point(), 1u);
+        assert_eq!(index.insert(0, 11), 2u);
+        assert_eq!(index.next_shared_point(), 3u);
+        assert_eq!(index.len(), 4u);
+    }
+
+    #[test]
+    fn next_shared_point_is_not_a_statement() {
+        let mut index = StmtIndex::new();
+        let point = index.next_shared_point();
+        // `point` is also a valid node id in this fake test data, but it
+        // was never `insert`ed, so it must not be mistaken for one.
+        assert!(!index.contains(point as ast::node_id));
+        assert_eq!(index.local_index_of(point as ast::node_id), None);
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2012 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// ----------------------------------------------------------------------
+// Pluggable statement passes
+//
+// `BorrowckCtxt::stmt_passes` lets compiler plugins register extra
+// closures that observe every statement gather-loans visits, alongside
+// the built-in `stmt_map` insertion, instead of re-walking the AST
+// themselves. A plugin pushes onto it at load time via
+// `Registry::register_stmt_pass`, the same way `rustc::plugin::Registry`
+// is mutated for syntax extensions and lint passes elsewhere in the
+// compiler; `add_stmt_to_map` then runs every registered pass, in
+// registration order, right after the built-in pass.
+//
+// `PassRegistry` itself is generic in the argument it hands each pass, so
+// it can be unit tested without constructing a real `@ast::stmt` or
+// `@BorrowckCtxt`. `StmtPasses` is the instantiation `BorrowckCtxt`
+// actually carries.
+
+use core::prelude::*;
+
+use middle::borrowck::BorrowckCtxt;
+use syntax::ast;
+
+pub struct PassRegistry<A> {
+    priv passes: ~[@fn(A)]
+}
+
+pub impl<A:Copy> PassRegistry<A> {
+    fn new() -> PassRegistry<A> {
+        PassRegistry { passes: ~[] }
+    }
+
+    /// Pushes `pass` onto the end of the registry; it will run after
+    /// every pass already registered.
+    fn register(&mut self, pass: @fn(A)) {
+        self.passes.push(pass);
+    }
+
+    fn len(&self) -> uint {
+        self.passes.len()
+    }
+
+    /// Runs every registered pass, in registration order, with `args`.
+    /// Does not run the built-in `stmt_map` insertion -- that stays
+    /// hardcoded in `add_stmt_to_map`, ahead of this call.
+    fn run_all(&self, args: A) {
+        for self.passes.each |&pass| {
+            (*pass)(args);
+        }
+    }
+}
+
+pub type StmtPasses = PassRegistry<(@ast::stmt, ast::node_id, @BorrowckCtxt)>;
+
+#[cfg(test)]
+mod test {
+    use super::PassRegistry;
+
+    #[test]
+    fn runs_registered_passes_in_order() {
+        let mut registry: PassRegistry<int> = PassRegistry::new();
+        let log: @mut ~[int] = @mut ~[];
+        registry.register(@|n: int| { log.push(n * 10); });
+        registry.register(@|n: int| { log.push(n * 100); });
+
+        registry.run_all(3);
+
+        assert_eq!(*log, ~[30, 300]);
+    }
+
+    #[test]
+    fn empty_registry_runs_nothing() {
+        let registry: PassRegistry<int> = PassRegistry::new();
+        assert_eq!(registry.len(), 0u);
+        registry.run_all(42); // must not fail with zero registered passes
+    }
+
+    #[test]
+    fn len_counts_registered_passes() {
+        let mut registry: PassRegistry<int> = PassRegistry::new();
+        registry.register(@|_n: int| {});
+        registry.register(@|_n: int| {});
+        assert_eq!(registry.len(), 2u);
+    }
+}